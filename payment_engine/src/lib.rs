@@ -0,0 +1,427 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::ops::{Add, Sub};
+
+use serde::de::{self, Deserializer};
+use serde::Deserialize;
+use thiserror::Error;
+
+/// A monetary amount stored as ten-thousandths of a unit, so that repeated
+/// deposits, withdrawals, disputes and resolutions never accumulate binary
+/// floating-point rounding error the way an `f64` balance would.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Amount {
+    /// The amount multiplied by 10_000, i.e. the value in ten-thousandths.
+    ten_thousandths: i64,
+}
+
+impl Amount {
+    const SCALE: i64 = 10_000;
+
+    /// Parses an amount from a CSV field such as `"1.5"` or `"12.3456"`.
+    /// A fractional part shorter than four digits is padded with zeros;
+    /// more than four fractional digits or a value that overflows an
+    /// `i64` once scaled is rejected.
+    pub fn parse(raw: &str) -> Result<Self, Box<dyn Error>> {
+        let raw = raw.trim();
+        let negative = raw.starts_with('-');
+        let unsigned = raw.strip_prefix('-').unwrap_or(raw);
+
+        let mut parts = unsigned.splitn(2, '.');
+        let whole_part = parts.next().unwrap_or("");
+        let frac_part = parts.next().unwrap_or("");
+
+        if frac_part.len() > 4 {
+            return Err(format!("Amount '{}' has more than four fractional digits", raw).into());
+        }
+        if !frac_part.chars().all(|c| c.is_ascii_digit()) || !whole_part.chars().all(|c| c.is_ascii_digit()) || whole_part.is_empty() {
+            return Err(format!("Amount '{}' is not a valid decimal number", raw).into());
+        }
+
+        let whole: i64 = whole_part.parse().map_err(|_| format!("Amount '{}' is not a valid decimal number", raw))?;
+        // Pad the fractional part out to four digits, e.g. "5" -> "5000".
+        let mut padded_frac = frac_part.to_string();
+        while padded_frac.len() < 4 {
+            padded_frac.push('0');
+        }
+        let frac: i64 = padded_frac.parse().map_err(|_| format!("Amount '{}' is not a valid decimal number", raw))?;
+
+        let whole_scaled = whole.checked_mul(Self::SCALE).ok_or_else(|| format!("Amount '{}' overflows", raw))?;
+        let mut ten_thousandths = whole_scaled.checked_add(frac).ok_or_else(|| format!("Amount '{}' overflows", raw))?;
+        if negative {
+            ten_thousandths = -ten_thousandths;
+        }
+
+        Ok(Amount { ten_thousandths })
+    }
+}
+
+impl Add for Amount {
+    type Output = Amount;
+
+    fn add(self, rhs: Amount) -> Amount {
+        Amount { ten_thousandths: self.ten_thousandths + rhs.ten_thousandths }
+    }
+}
+
+impl Sub for Amount {
+    type Output = Amount;
+
+    fn sub(self, rhs: Amount) -> Amount {
+        Amount { ten_thousandths: self.ten_thousandths - rhs.ten_thousandths }
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let negative = self.ten_thousandths < 0;
+        let magnitude = self.ten_thousandths.abs();
+        let whole = magnitude / Amount::SCALE;
+        let frac = magnitude % Amount::SCALE;
+        if negative {
+            write!(f, "-{}.{:04}", whole, frac)
+        } else {
+            write!(f, "{}.{:04}", whole, frac)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Amount {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let raw = String::deserialize(deserializer)?;
+        Amount::parse(&raw).map_err(de::Error::custom)
+    }
+}
+
+/// The lifecycle of a processed deposit or withdrawal. A transaction starts
+/// `Processed`, can be put into `Disputed`, and from there resolves to either
+/// `Resolved` (which may be disputed again) or `ChargedBack`, which is terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxState {
+    Processed,
+    Disputed,
+    Resolved,
+    ChargedBack,
+}
+
+/// The raw shape of one CSV row. `amount` is optional because dispute,
+/// resolve and chargeback rows legally omit the trailing amount column
+/// (the reader is configured with `.flexible(true)` to allow this).
+#[derive(Debug, Deserialize)]
+pub struct Record {
+    #[serde(rename = "type")]
+    kind: String,
+    client: String,
+    tx: String,
+    amount: Option<Amount>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Transaction {
+    Deposit { client_id: String, transaction_id: String, amount: Amount },
+    Withdrawal { client_id: String, transaction_id: String, amount: Amount },
+    Dispute { client_id: String, transaction_id: String },
+    Resolve { client_id: String, transaction_id: String },
+    Chargeback { client_id: String, transaction_id: String },
+}
+
+impl Transaction {
+    fn client_id(&self) -> &str {
+        match self {
+            Transaction::Deposit { client_id, .. }
+            | Transaction::Withdrawal { client_id, .. }
+            | Transaction::Dispute { client_id, .. }
+            | Transaction::Resolve { client_id, .. }
+            | Transaction::Chargeback { client_id, .. } => client_id,
+        }
+    }
+}
+
+impl TryFrom<Record> for Transaction {
+    type Error = Box<dyn Error>;
+
+    fn try_from(record: Record) -> Result<Self, Self::Error> {
+        // Check if the supplied IDs are valid integers, even though we use them
+        // as strings in this implementation. This should also catch empty values.
+        if record.client.parse::<u16>().is_err() {
+            return Err(format!("Client ID '{}' is not a valid integer", record.client).into());
+        }
+        if record.tx.parse::<u32>().is_err() {
+            return Err(format!("Transaction ID '{}' is not a valid integer", record.tx).into());
+        }
+
+        let client_id = record.client;
+        let transaction_id = record.tx;
+
+        match record.kind.as_str() {
+            "deposit" => {
+                let amount = record.amount.ok_or_else(|| format!("Deposit {} is missing an amount", transaction_id))?;
+                Ok(Transaction::Deposit { client_id, transaction_id, amount })
+            }
+            "withdrawal" => {
+                let amount = record.amount.ok_or_else(|| format!("Withdrawal {} is missing an amount", transaction_id))?;
+                Ok(Transaction::Withdrawal { client_id, transaction_id, amount })
+            }
+            "dispute" => {
+                if record.amount.is_some() {
+                    return Err(format!("Dispute {} must not carry an amount", transaction_id).into());
+                }
+                Ok(Transaction::Dispute { client_id, transaction_id })
+            }
+            "resolve" => {
+                if record.amount.is_some() {
+                    return Err(format!("Resolve {} must not carry an amount", transaction_id).into());
+                }
+                Ok(Transaction::Resolve { client_id, transaction_id })
+            }
+            "chargeback" => {
+                if record.amount.is_some() {
+                    return Err(format!("Chargeback {} must not carry an amount", transaction_id).into());
+                }
+                Ok(Transaction::Chargeback { client_id, transaction_id })
+            }
+            other => Err(format!("Unknown transaction type '{}'", other).into()),
+        }
+    }
+}
+
+/// The record kept for a processed deposit or withdrawal, used to replay its
+/// effect when a dispute, resolve or chargeback references it later.
+#[derive(Debug, Clone)]
+pub struct TxRecord {
+    pub client_id: String,
+    pub amount: Amount,
+    pub state: TxState,
+}
+
+/// A client's account balances.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Account {
+    pub available: Amount,
+    pub held: Amount,
+    pub frozen: bool,
+}
+
+impl Account {
+    pub fn total(&self) -> Amount {
+        self.available + self.held
+    }
+}
+
+/// A final snapshot of one client's account, as printed in the output report.
+#[derive(Debug, Clone)]
+pub struct AccountSnapshot {
+    pub client_id: String,
+    pub available: Amount,
+    pub held: Amount,
+    pub total: Amount,
+    pub locked: bool,
+}
+
+/// Errors that can occur while processing a transaction against the ledger.
+#[derive(Debug, Error)]
+pub enum LedgerError {
+    #[error("transaction {0} not found in transaction history")]
+    UnknownTx(String),
+    #[error("transaction {0} is already disputed")]
+    AlreadyDisputed(String),
+    #[error("transaction {0} is not currently disputed")]
+    NotDisputed(String),
+    #[error("account {0} is frozen")]
+    FrozenAccount(String),
+    #[error("not enough funds for withdrawal, transaction {0}")]
+    NotEnoughFunds(String),
+}
+
+/// Storage backing a `Ledger`: owns every client account and every processed
+/// transaction. `InMemoryStore` is the default, but implementing this trait
+/// against a disk- or database-backed store (e.g. a SQL table keyed by
+/// `(client, tx)`) lets `Ledger` scale to inputs that don't fit in memory,
+/// without the processing logic in `Ledger` changing at all.
+pub trait AccountStore {
+    fn get_account(&self, client_id: &str) -> Option<Account>;
+    fn upsert_account(&mut self, client_id: &str, account: Account);
+    /// Records a new transaction. Returns `false` without overwriting
+    /// anything if `transaction_id` is already recorded.
+    fn record_tx(&mut self, transaction_id: &str, client_id: &str, amount: Amount, state: TxState) -> bool;
+    fn get_tx(&self, transaction_id: &str) -> Option<TxRecord>;
+    fn set_tx_state(&mut self, transaction_id: &str, state: TxState);
+    /// An iterator over every account the store has seen.
+    fn accounts(&self) -> Box<dyn Iterator<Item = (String, Account)> + '_>;
+}
+
+/// The default, in-memory `AccountStore`, backed by two `HashMap`s.
+#[derive(Default)]
+pub struct InMemoryStore {
+    accounts: HashMap<String, Account>,
+    transactions: HashMap<String, TxRecord>,
+}
+
+impl AccountStore for InMemoryStore {
+    fn get_account(&self, client_id: &str) -> Option<Account> {
+        self.accounts.get(client_id).copied()
+    }
+
+    fn upsert_account(&mut self, client_id: &str, account: Account) {
+        self.accounts.insert(client_id.to_string(), account);
+    }
+
+    fn record_tx(&mut self, transaction_id: &str, client_id: &str, amount: Amount, state: TxState) -> bool {
+        if self.transactions.contains_key(transaction_id) {
+            return false;
+        }
+        self.transactions.insert(transaction_id.to_string(), TxRecord {
+            client_id: client_id.to_string(),
+            amount,
+            state,
+        });
+        true
+    }
+
+    fn get_tx(&self, transaction_id: &str) -> Option<TxRecord> {
+        self.transactions.get(transaction_id).cloned()
+    }
+
+    fn set_tx_state(&mut self, transaction_id: &str, state: TxState) {
+        if let Some(record) = self.transactions.get_mut(transaction_id) {
+            record.state = state;
+        }
+    }
+
+    fn accounts(&self) -> Box<dyn Iterator<Item = (String, Account)> + '_> {
+        Box::new(self.accounts.iter().map(|(client_id, account)| (client_id.clone(), *account)))
+    }
+}
+
+/// The payments engine: owns an `AccountStore` and applies each incoming
+/// `Transaction` to it in order.
+pub struct Ledger<S: AccountStore = InMemoryStore> {
+    store: S,
+}
+
+impl Ledger<InMemoryStore> {
+    pub fn new() -> Self {
+        Ledger { store: InMemoryStore::default() }
+    }
+}
+
+impl Default for Ledger<InMemoryStore> {
+    fn default() -> Self {
+        Ledger::new()
+    }
+}
+
+impl<S: AccountStore> Ledger<S> {
+    /// Builds a `Ledger` on top of an arbitrary `AccountStore`, e.g. a
+    /// disk- or database-backed implementation for large inputs.
+    pub fn with_store(store: S) -> Self {
+        Ledger { store }
+    }
+
+    /// Applies a single transaction to the ledger.
+    pub fn process(&mut self, tx: Transaction) -> Result<(), LedgerError> {
+        if let Some(account) = self.store.get_account(tx.client_id()) {
+            if account.frozen {
+                return Err(LedgerError::FrozenAccount(tx.client_id().to_string()));
+            }
+        }
+
+        match tx {
+            Transaction::Deposit { client_id, transaction_id, amount } => self.deposit(client_id, transaction_id, amount),
+            Transaction::Withdrawal { client_id, transaction_id, amount } => self.withdraw(client_id, transaction_id, amount),
+            Transaction::Dispute { client_id, transaction_id } => self.dispute(client_id, transaction_id),
+            Transaction::Resolve { client_id, transaction_id } => self.finalize_dispute(client_id, transaction_id, TxState::Resolved),
+            Transaction::Chargeback { client_id, transaction_id } => self.finalize_dispute(client_id, transaction_id, TxState::ChargedBack),
+        }
+    }
+
+    /// An iterator over the final snapshot of every account touched so far.
+    pub fn snapshots(&self) -> impl Iterator<Item = AccountSnapshot> + '_ {
+        self.store.accounts().map(|(client_id, account)| AccountSnapshot {
+            client_id,
+            available: account.available,
+            held: account.held,
+            total: account.total(),
+            locked: account.frozen,
+        })
+    }
+
+    fn deposit(&mut self, client_id: String, transaction_id: String, amount: Amount) -> Result<(), LedgerError> {
+        // Transaction ids are assumed unique; treat a repeat as a no-op rather
+        // than letting a later dispute reference a second, overwritten amount.
+        if self.store.get_tx(&transaction_id).is_some() {
+            return Ok(());
+        }
+        let mut account = self.store.get_account(&client_id).unwrap_or_default();
+        account.available = account.available + amount;
+        self.store.upsert_account(&client_id, account);
+        self.store.record_tx(&transaction_id, &client_id, amount, TxState::Processed);
+        Ok(())
+    }
+
+    fn withdraw(&mut self, client_id: String, transaction_id: String, amount: Amount) -> Result<(), LedgerError> {
+        if self.store.get_tx(&transaction_id).is_some() {
+            return Ok(());
+        }
+        let mut account = self.store.get_account(&client_id).unwrap_or_default();
+        // Reject withdrawals that would take the available balance negative,
+        // leaving the account untouched and the tx id unrecorded.
+        if amount > account.available {
+            return Err(LedgerError::NotEnoughFunds(transaction_id));
+        }
+        account.available = account.available - amount;
+        self.store.upsert_account(&client_id, account);
+        self.store.record_tx(&transaction_id, &client_id, amount, TxState::Processed);
+        Ok(())
+    }
+
+    fn dispute(&mut self, client_id: String, transaction_id: String) -> Result<(), LedgerError> {
+        let record = self.store.get_tx(&transaction_id)
+            .ok_or_else(|| LedgerError::UnknownTx(transaction_id.clone()))?;
+        if record.client_id != client_id {
+            return Err(LedgerError::UnknownTx(transaction_id));
+        }
+        // Only a transaction that is not currently disputed may enter dispute; a
+        // charged-back transaction can never be disputed again.
+        match record.state {
+            TxState::Disputed | TxState::ChargedBack => return Err(LedgerError::AlreadyDisputed(transaction_id)),
+            TxState::Processed | TxState::Resolved => {}
+        }
+
+        let mut account = self.store.get_account(&client_id).unwrap_or_default();
+        account.available = account.available - record.amount;
+        account.held = account.held + record.amount;
+        self.store.upsert_account(&client_id, account);
+        self.store.set_tx_state(&transaction_id, TxState::Disputed);
+        Ok(())
+    }
+
+    fn finalize_dispute(&mut self, client_id: String, transaction_id: String, into: TxState) -> Result<(), LedgerError> {
+        let record = self.store.get_tx(&transaction_id)
+            .ok_or_else(|| LedgerError::UnknownTx(transaction_id.clone()))?;
+        if record.client_id != client_id || record.state != TxState::Disputed {
+            return Err(LedgerError::NotDisputed(transaction_id));
+        }
+
+        let mut account = self.store.get_account(&client_id).unwrap_or_default();
+        match into {
+            TxState::Resolved => {
+                account.available = account.available + record.amount;
+                account.held = account.held - record.amount;
+            }
+            TxState::ChargedBack => {
+                account.held = account.held - record.amount;
+                account.frozen = true;
+            }
+            TxState::Processed | TxState::Disputed => unreachable!("finalize_dispute only transitions to Resolved or ChargedBack"),
+        }
+        self.store.upsert_account(&client_id, account);
+        self.store.set_tx_state(&transaction_id, into);
+        Ok(())
+    }
+}